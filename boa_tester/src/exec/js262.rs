@@ -1,21 +1,40 @@
+use std::{
+    cell::RefCell,
+    collections::VecDeque,
+    rc::Rc,
+    sync::Mutex,
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
 use boa_engine::{
     builtins::JsArgs,
-    object::{JsObject, ObjectInitializer},
+    native_function::NativeFunction,
+    object::{FunctionObjectBuilder, JsObject, ObjectInitializer},
     property::Attribute,
-    Context, JsNativeError, JsResult, JsValue, Source,
+    Context, JsNativeError, JsResult, JsString, JsValue, Script, Source,
 };
+use boa_gc::{Finalize, Trace};
+use once_cell::sync::Lazy;
+
+/// A realm created by `$262.createRealm()`, kept alive for as long as its `$262` object is
+/// reachable so that calling e.g. `other262.evalScript(...)` from a different realm still
+/// evaluates against this one.
+type Realm = Rc<RefCell<Context<'static>>>;
 
 /// Initializes the object in the context.
 pub(super) fn init(context: &mut Context<'_>) -> JsObject {
     let global_obj = context.global_object().clone();
+    let agent = init_agent(context);
 
     let obj = ObjectInitializer::new(context)
         .function(create_realm, "createRealm", 0)
         .function(detach_array_buffer, "detachArrayBuffer", 2)
         .function(eval_script, "evalScript", 1)
         .function(gc, "gc", 0)
+        .function(clear_kept_objects, "clearKeptObjects", 0)
         .property("global", global_obj, Attribute::default())
-        // .property("agent", agent, Attribute::default())
+        .property("agent", agent, Attribute::default())
         .build();
 
     context.register_global_property("$262", obj.clone(), Attribute::empty());
@@ -29,22 +48,134 @@ pub(super) fn init(context: &mut Context<'_>) -> JsObject {
 /// returns the `$262` property of the new realm's global object.
 #[allow(clippy::unnecessary_wraps)]
 fn create_realm(_this: &JsValue, _: &[JsValue], _context: &mut Context<'_>) -> JsResult<JsValue> {
-    let mut context = Context::default();
+    let realm: Realm = Rc::new(RefCell::new(Context::default()));
+    Ok(JsValue::new(init_realm(&realm)))
+}
 
-    // add the $262 object.
-    let js_262 = init(&mut context);
+/// Builds the full `$262` harness for `realm` and registers it as `realm`'s own global
+/// `$262` property.
+///
+/// Unlike [`init`], every `$262` function here other than the `agent` ones (which only touch
+/// process-wide state) is a closure over `realm` rather than a plain
+/// function pointer, so it keeps operating on `realm` no matter which realm ends up calling
+/// it -- e.g. `other262.evalScript(...)` must run in `other262`'s realm, not the caller's.
+/// This is also what keeps `realm`'s `Context` alive: it lives on for as long as the
+/// closures (and so the returned `$262` object) are reachable.
+///
+/// This installs `createRealm`, `detachArrayBuffer`, `evalScript`, `gc`, `clearKeptObjects`,
+/// `agent`, and `global`. BLOCKED: `$262.IsHTMLDDA`, which needs an exotic object with its
+/// own `[[IsHTMLDDA]]` internal slot that `boa_engine` doesn't support yet.
+fn init_realm(realm: &Realm) -> JsObject {
+    let mut ctx = realm.borrow_mut();
+    let global_obj = ctx.global_object().clone();
+    let agent = init_agent(&mut ctx);
 
-    Ok(JsValue::new(js_262))
+    let create_realm_fn = realm_function(&mut ctx, realm, "createRealm", 0, |_args, _context| {
+        let child: Realm = Rc::new(RefCell::new(Context::default()));
+        Ok(JsValue::new(init_realm(&child)))
+    });
+    let detach_array_buffer_fn = realm_function(
+        &mut ctx,
+        realm,
+        "detachArrayBuffer",
+        2,
+        detach_array_buffer_impl,
+    );
+    let eval_script_fn = realm_function(&mut ctx, realm, "evalScript", 1, eval_script_impl);
+    let gc_fn = realm_function(&mut ctx, realm, "gc", 0, |_args, _context| {
+        boa_gc::force_collect();
+        Ok(JsValue::undefined())
+    });
+    let clear_kept_objects_fn =
+        realm_function(&mut ctx, realm, "clearKeptObjects", 0, |_args, context| {
+            context.clear_kept_objects();
+            Ok(JsValue::undefined())
+        });
+
+    let obj = ObjectInitializer::new(&mut ctx)
+        .property("createRealm", create_realm_fn, Attribute::default())
+        .property(
+            "detachArrayBuffer",
+            detach_array_buffer_fn,
+            Attribute::default(),
+        )
+        .property("evalScript", eval_script_fn, Attribute::default())
+        .property("gc", gc_fn, Attribute::default())
+        .property(
+            "clearKeptObjects",
+            clear_kept_objects_fn,
+            Attribute::default(),
+        )
+        .property("global", global_obj, Attribute::default())
+        .property("agent", agent, Attribute::default())
+        .build();
+
+    ctx.register_global_property("$262", obj.clone(), Attribute::empty());
+
+    obj
 }
 
-/// The `$262.detachArrayBuffer()` function.
+/// The capture list for a [`realm_function`] closure.
 ///
-/// Implements the `DetachArrayBuffer` abstract operation.
+/// `#[unsafe_ignore_trace]` is sound on `realm` because `Realm` (`Rc<RefCell<Context<'static>>>`)
+/// is a plain Rust container, not a `Gc<T>` -- there is nothing here for `Trace` to walk. The
+/// `Gc` values inside the `Context` it wraps are rooted independently by that `Context`, the
+/// same way the top-level `Context` every test runs in already is.
+#[derive(Clone, Trace, Finalize)]
+struct RealmCapture {
+    #[unsafe_ignore_trace]
+    realm: Realm,
+}
+
+/// Builds a `Function` object named `name` whose behavior always runs against `realm`,
+/// ignoring whatever `Context` the engine passes in at the call site -- necessary because a
+/// realm's `$262` object is typically invoked *from* a different realm (e.g.
+/// `other262.evalScript(...)`), and it must still run against its own realm, not the
+/// caller's.
+///
+/// Reentrant into the same realm's own `$262` (e.g.
+/// `other262.evalScript("$262.evalScript('1')")`, where the inner `$262` resolves right back
+/// to `other262`'s) without double-borrowing `realm`: when `realm` is already borrowed, the
+/// `Context` the engine handed us for this call is that same borrow, so reuse it instead.
+fn realm_function(
+    context: &mut Context<'_>,
+    realm: &Realm,
+    name: &str,
+    length: usize,
+    f: impl Fn(&[JsValue], &mut Context<'_>) -> JsResult<JsValue> + 'static,
+) -> JsObject {
+    let captures = RealmCapture {
+        realm: realm.clone(),
+    };
+    let function = NativeFunction::from_closure_with_captures(
+        move |_this, args, captures, context| match captures.realm.try_borrow_mut() {
+            Ok(mut realm_context) => f(args, &mut realm_context),
+            Err(_) => f(args, context),
+        },
+        captures,
+    );
+
+    FunctionObjectBuilder::new(context.realm(), function)
+        .name(name)
+        .length(length)
+        .build()
+}
+
+/// The `$262.detachArrayBuffer()` function.
 fn detach_array_buffer(
     _this: &JsValue,
     args: &[JsValue],
-    _: &mut Context<'_>,
+    context: &mut Context<'_>,
 ) -> JsResult<JsValue> {
+    detach_array_buffer_impl(args, context)
+}
+
+/// Implements the `DetachArrayBuffer` abstract operation.
+///
+/// BLOCKED: `ArrayBuffer.prototype.transfer`/`transferToFixedLength` need this operation
+/// exposed on `boa_engine`'s `ArrayBuffer` itself (as e.g. `copy_and_detach`); neither that
+/// nor the prototype methods exist yet, so this harness still runs the inline algorithm.
+fn detach_array_buffer_impl(args: &[JsValue], _context: &mut Context<'_>) -> JsResult<JsValue> {
     fn type_err() -> JsNativeError {
         JsNativeError::typ().with_message("The provided object was not an ArrayBuffer")
     }
@@ -78,22 +209,28 @@ fn detach_array_buffer(
 }
 
 /// The `$262.evalScript()` function.
-///
-/// Accepts a string value as its first argument and executes it as an ECMAScript script.
 fn eval_script(_this: &JsValue, args: &[JsValue], context: &mut Context<'_>) -> JsResult<JsValue> {
-    args.get(0).and_then(JsValue::as_string).map_or_else(
-        || Ok(JsValue::undefined()),
-        |source_text| match context.parse(Source::from_bytes(&source_text.to_std_string_escaped()))
-        {
-            // TODO: check strict
-            Err(e) => Err(JsNativeError::typ()
-                .with_message(format!("Uncaught Syntax Error: {e}"))
-                .into()),
-            // Calling eval here parses the code a second time.
-            // TODO: We can fix this after we have have defined the public api for the vm executer.
-            Ok(_) => context.eval(Source::from_bytes(&source_text.to_std_string_escaped())),
-        },
+    eval_script_impl(args, context)
+}
+
+/// Accepts a string value as its first argument and runs it as a fresh top-level Script,
+/// honoring its own `"use strict"` directive rather than inheriting the caller's strictness
+/// the way an indirect `eval` would.
+fn eval_script_impl(args: &[JsValue], context: &mut Context<'_>) -> JsResult<JsValue> {
+    let Some(source_text) = args.get(0).and_then(JsValue::as_string) else {
+        return Ok(JsValue::undefined());
+    };
+
+    // Parse once: building the `Script` also validates it, so there's no need for a second,
+    // throwaway parse inside `eval`.
+    let script = Script::parse(
+        Source::from_bytes(&source_text.to_std_string_escaped()),
+        None,
+        context,
     )
+    .map_err(|e| JsNativeError::syntax().with_message(format!("Uncaught SyntaxError: {e}")))?;
+
+    script.evaluate(context)
 }
 
 /// The `$262.gc()` function.
@@ -106,3 +243,343 @@ fn gc(_this: &JsValue, _: &[JsValue], _context: &mut Context<'_>) -> JsResult<Js
     boa_gc::force_collect();
     Ok(JsValue::undefined())
 }
+
+/// The `$262.clearKeptObjects()` function.
+///
+/// Implements the `ClearKeptObjects` abstract operation: empties the list of objects the
+/// engine is keeping strongly reachable between microtask checkpoints (the `[[KeptAlive]]`
+/// list, populated whenever a `WeakRef` is dereferenced). Tests call this immediately before
+/// `$262.gc()` so a dereferenced `WeakRef` target can become collectible mid-job, rather than
+/// staying alive until the next real checkpoint.
+#[allow(clippy::unnecessary_wraps)]
+fn clear_kept_objects(
+    _this: &JsValue,
+    _: &[JsValue],
+    context: &mut Context<'_>,
+) -> JsResult<JsValue> {
+    context.clear_kept_objects();
+    Ok(JsValue::undefined())
+}
+
+/// Process-wide state backing the `$262.agent` API.
+///
+/// Every agent -- the implicit main agent and every one spawned by `agent.start` -- builds
+/// its own [`Context`] independently, so the state that must be visible to all of them (the
+/// report queue, the handles of the spawned agent threads) lives in a single static rather than
+/// being threaded through `Context`.
+#[derive(Debug)]
+struct AgentState {
+    /// Handles for every agent thread spawned so far, so the process can outlive them.
+    threads: Mutex<Vec<JoinHandle<()>>>,
+    /// Messages queued by `report` and drained (FIFO) by `getReport`.
+    reports: Mutex<VecDeque<String>>,
+    /// Reference point for `monotonicNow`.
+    start: Instant,
+}
+
+static AGENT: Lazy<AgentState> = Lazy::new(|| AgentState {
+    threads: Mutex::new(Vec::new()),
+    reports: Mutex::new(VecDeque::new()),
+    start: Instant::now(),
+});
+
+/// Builds the `$262.agent` object, implementing the test262 "Agent" model used by
+/// `Atomics`/`SharedArrayBuffer` tests to drive multiple cooperating agents.
+///
+/// `broadcast`/`receiveBroadcast` are BLOCKED on `boa_engine` support (see
+/// [`agent_broadcast`]), so cross-agent `Atomics`/`SharedArrayBuffer` tests cannot run yet.
+fn init_agent(context: &mut Context<'_>) -> JsObject {
+    ObjectInitializer::new(context)
+        .function(agent_start, "start", 1)
+        .function(agent_broadcast, "broadcast", 1)
+        .function(agent_get_report, "getReport", 0)
+        .function(agent_sleep, "sleep", 1)
+        .function(agent_monotonic_now, "monotonicNow", 0)
+        .function(agent_receive_broadcast, "receiveBroadcast", 1)
+        .function(agent_report, "report", 1)
+        .function(agent_leaving, "leaving", 0)
+        .build()
+}
+
+/// The `$262.agent.start()` function.
+///
+/// Spawns a new agent on its own OS thread: a fresh `Context` with the full `$262` harness
+/// installed, running `src` as a script.
+fn agent_start(_this: &JsValue, args: &[JsValue], _: &mut Context<'_>) -> JsResult<JsValue> {
+    let src = args
+        .get_or_undefined(0)
+        .as_string()
+        .ok_or_else(|| JsNativeError::typ().with_message("agent.start expects a string source"))?
+        .to_std_string_escaped();
+
+    let handle = thread::Builder::new()
+        .name("boa-test262-agent".into())
+        .spawn(move || {
+            let mut context = Context::default();
+            init(&mut context);
+            if let Err(e) = context.eval(Source::from_bytes(src.as_bytes())) {
+                eprintln!("agent thread errored: {e}");
+            }
+        })
+        .expect("failed to spawn agent thread");
+
+    AGENT.threads.lock().expect("poisoned").push(handle);
+
+    Ok(JsValue::undefined())
+}
+
+/// The `$262.agent.broadcast()` function.
+///
+/// BLOCKED: requires `boa_engine`'s `ArrayBuffer` to back `array_buffer_data` with an
+/// `Arc`-backed region for shared buffers, which doesn't exist yet. Reports the gap rather
+/// than silently no-op'ing.
+fn agent_broadcast(_this: &JsValue, _args: &[JsValue], _: &mut Context<'_>) -> JsResult<JsValue> {
+    Err(JsNativeError::typ()
+        .with_message(
+            "agent.broadcast is not yet implemented: it requires boa_engine's ArrayBuffer to \
+             expose Arc-backed shared storage",
+        )
+        .into())
+}
+
+/// The `$262.agent.getReport()` function.
+///
+/// Pops the oldest pending message queued by some agent's `report`, or `null` if none is
+/// available yet.
+#[allow(clippy::unnecessary_wraps)]
+fn agent_get_report(_this: &JsValue, _: &[JsValue], _: &mut Context<'_>) -> JsResult<JsValue> {
+    Ok(AGENT
+        .reports
+        .lock()
+        .expect("poisoned")
+        .pop_front()
+        .map_or(JsValue::null(), |msg| JsValue::new(JsString::from(msg))))
+}
+
+/// The `$262.agent.sleep()` function.
+fn agent_sleep(_this: &JsValue, args: &[JsValue], context: &mut Context<'_>) -> JsResult<JsValue> {
+    let ms = args.get_or_undefined(0).to_number(context)?;
+    if !ms.is_finite() {
+        return Err(JsNativeError::typ()
+            .with_message("agent.sleep expects a finite number of milliseconds")
+            .into());
+    }
+
+    thread::sleep(duration_from_millis_saturating(ms));
+    Ok(JsValue::undefined())
+}
+
+/// Converts a non-negative, finite millisecond count to a `Duration`, saturating to
+/// `Duration::MAX` instead of panicking when it doesn't fit (`Duration::from_secs_f64` panics
+/// on such values).
+fn duration_from_millis_saturating(ms: f64) -> Duration {
+    let secs = ms.max(0.0) / 1000.0;
+    Duration::try_from_secs_f64(secs).unwrap_or(Duration::MAX)
+}
+
+/// The `$262.agent.monotonicNow()` function.
+#[allow(clippy::unnecessary_wraps)]
+fn agent_monotonic_now(_this: &JsValue, _: &[JsValue], _: &mut Context<'_>) -> JsResult<JsValue> {
+    Ok(JsValue::new(AGENT.start.elapsed().as_secs_f64() * 1000.0))
+}
+
+/// The `$262.agent.receiveBroadcast()` function.
+///
+/// BLOCKED: see [`agent_broadcast`]. Reports the same gap instead of blocking forever
+/// waiting for a broadcast that can never arrive.
+fn agent_receive_broadcast(
+    _this: &JsValue,
+    args: &[JsValue],
+    _: &mut Context<'_>,
+) -> JsResult<JsValue> {
+    args.get_or_undefined(0)
+        .as_object()
+        .filter(|f| f.is_callable())
+        .ok_or_else(|| {
+            JsNativeError::typ().with_message("agent.receiveBroadcast expects a function")
+        })?;
+
+    Err(JsNativeError::typ()
+        .with_message(
+            "agent.receiveBroadcast is not yet implemented: it requires boa_engine's \
+             ArrayBuffer to expose Arc-backed shared storage",
+        )
+        .into())
+}
+
+/// The `$262.agent.report()` function.
+fn agent_report(_this: &JsValue, args: &[JsValue], context: &mut Context<'_>) -> JsResult<JsValue> {
+    let msg = args
+        .get_or_undefined(0)
+        .to_string(context)?
+        .to_std_string_escaped();
+    AGENT.reports.lock().expect("poisoned").push_back(msg);
+    Ok(JsValue::undefined())
+}
+
+/// The `$262.agent.leaving()` function.
+///
+/// No-op: excludes this agent from a future `broadcast`'s wait set, but `broadcast` is
+/// BLOCKED (see [`agent_broadcast`]), so there is nothing yet to exclude it from. Kept so
+/// scripts that call it don't fail with "not a function".
+#[allow(clippy::unnecessary_wraps)]
+fn agent_leaving(_this: &JsValue, _: &[JsValue], _: &mut Context<'_>) -> JsResult<JsValue> {
+    Ok(JsValue::undefined())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn new_262_context() -> Context<'static> {
+        let mut context = Context::default();
+        init(&mut context);
+        context
+    }
+
+    #[test]
+    fn duration_from_millis_saturating_does_not_panic_on_unrepresentable_values() {
+        assert_eq!(duration_from_millis_saturating(1e30), Duration::MAX);
+        assert_eq!(duration_from_millis_saturating(-1.0), Duration::ZERO);
+        assert_eq!(
+            duration_from_millis_saturating(1000.0),
+            Duration::from_secs(1)
+        );
+    }
+
+    #[test]
+    fn agent_broadcast_reports_unimplemented_instead_of_aliasing() {
+        let mut context = new_262_context();
+
+        // `agent.broadcast` can't actually alias a `SharedArrayBuffer` across threads yet (see
+        // `agent_broadcast`'s doc comment), so it must fail loudly rather than pretend to work.
+        let result = context.eval(Source::from_bytes(
+            b"var sab = new SharedArrayBuffer(4); $262.agent.broadcast(sab, 1);",
+        ));
+        assert!(
+            result.is_err(),
+            "agent.broadcast should report the missing Arc-backed storage, not silently succeed"
+        );
+
+        let result = context.eval(Source::from_bytes(
+            b"$262.agent.receiveBroadcast(function () {});",
+        ));
+        assert!(
+            result.is_err(),
+            "agent.receiveBroadcast should report the same gap instead of blocking forever"
+        );
+    }
+
+    #[test]
+    fn clear_kept_objects_lets_a_dereferenced_weak_ref_be_collected() {
+        let mut context = new_262_context();
+        let result = context
+            .eval(Source::from_bytes(
+                br#"
+                    let target = {};
+                    let ref_ = new WeakRef(target);
+                    ref_.deref();
+                    target = null;
+                    $262.clearKeptObjects();
+                    $262.gc();
+                    ref_.deref() === undefined;
+                "#,
+            ))
+            .expect("script should evaluate");
+        assert_eq!(
+            result.as_boolean(),
+            Some(true),
+            "clearKeptObjects should drop the WeakRef's strong hold so gc() can reclaim target"
+        );
+    }
+
+    #[test]
+    fn eval_script_honors_its_own_strictness_and_distinguishes_syntax_from_runtime_errors() {
+        let mut context = new_262_context();
+
+        let strict = context
+            .eval(Source::from_bytes(
+                br#"$262.evalScript('"use strict"; (function () { return this; })() === undefined');"#,
+            ))
+            .expect("strict script should evaluate");
+        assert_eq!(strict.as_boolean(), Some(true));
+
+        let sloppy = context
+            .eval(Source::from_bytes(
+                b"$262.evalScript('(function () { return this; })() === this');",
+            ))
+            .expect("sloppy script should evaluate");
+        assert_eq!(sloppy.as_boolean(), Some(true));
+
+        let syntax_error = context
+            .eval(Source::from_bytes(b"$262.evalScript('(');"))
+            .expect_err("unparsable source should fail to parse");
+        assert!(
+            syntax_error
+                .as_native()
+                .is_some_and(|e| matches!(e.kind(), boa_engine::JsNativeErrorKind::Syntax)),
+            "invalid syntax should surface as a SyntaxError, got {syntax_error:?}"
+        );
+
+        let type_error = context
+            .eval(Source::from_bytes(b"$262.evalScript('null.x');"))
+            .expect_err("dereferencing null should fail at runtime");
+        assert!(
+            type_error
+                .as_native()
+                .is_some_and(|e| matches!(e.kind(), boa_engine::JsNativeErrorKind::Type)),
+            "a runtime error in the evaluated script should surface as a TypeError, got {type_error:?}"
+        );
+    }
+
+    #[test]
+    fn create_realm_gives_each_realm_its_own_global_identity() {
+        let mut context = new_262_context();
+        let result = context
+            .eval(Source::from_bytes(
+                br#"
+                    var other262 = $262.createRealm();
+                    Array !== other262.global.Array;
+                "#,
+            ))
+            .expect("script should evaluate");
+        assert_eq!(
+            result.as_boolean(),
+            Some(true),
+            "each realm created by createRealm() should have its own distinct intrinsics"
+        );
+    }
+
+    #[test]
+    fn other_realm_eval_script_actually_runs_in_the_child_realm() {
+        let mut context = new_262_context();
+        let result = context
+            .eval(Source::from_bytes(
+                br#"
+                    var other262 = $262.createRealm();
+                    other262.evalScript('[]') instanceof other262.global.Array
+                        && !(other262.evalScript('[]') instanceof Array);
+                "#,
+            ))
+            .expect("script should evaluate");
+        assert_eq!(
+            result.as_boolean(),
+            Some(true),
+            "other262.evalScript(...) should construct values using the child realm's intrinsics"
+        );
+    }
+
+    #[test]
+    fn eval_script_is_reentrant_into_the_same_realms_262() {
+        let mut context = new_262_context();
+        let result = context
+            .eval(Source::from_bytes(
+                br#"
+                    var other262 = $262.createRealm();
+                    other262.evalScript("$262.evalScript('1 + 1')");
+                "#,
+            ))
+            .expect("a realm's $262 calling back into itself should not panic");
+        assert_eq!(result.as_number(), Some(2.0));
+    }
+}